@@ -0,0 +1,59 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `TransFnAttrs`/`TransFnAttrFlags`: the result of the `trans_fn_attrs`
+//! query, collected per-item by `librustc_typeck::collect` from the
+//! translation-affecting attributes on that item and consulted by
+//! `librustc_trans::back::symbol_export` when deciding a symbol's export
+//! level.
+
+use syntax::symbol::Symbol;
+
+bitflags! {
+    pub struct TransFnAttrFlags: u32 {
+        /// `#[no_mangle]`: the symbol keeps its Rust name unmangled, which
+        /// makes it externally visible regardless of what reaches it.
+        const NO_MANGLE                 = 1 << 0;
+        /// Marks symbols that are plumbing internal to `core`/`std`/the
+        /// allocator shims rather than public API. `symbol_export_level`
+        /// keeps these at `Rust` level even when `#[no_mangle]`d.
+        const RUSTC_STD_INTERNAL_SYMBOL = 1 << 1;
+        /// `#[export_level = "c"]`: force `SymbolExportLevel::C`, overriding
+        /// the extern/std-internal heuristic in `symbol_export_level`.
+        const EXPORT_LEVEL_C            = 1 << 2;
+        /// `#[export_level = "rust"]`: force `SymbolExportLevel::Rust`.
+        const EXPORT_LEVEL_RUST         = 1 << 3;
+    }
+}
+
+/// The translation-affecting attributes collected for a single item.
+#[derive(Clone)]
+pub struct TransFnAttrs {
+    pub flags: TransFnAttrFlags,
+    /// The explicit symbol name from `#[export_name = "..."]`, if any.
+    pub export_name: Option<Symbol>,
+}
+
+impl TransFnAttrs {
+    pub fn new() -> TransFnAttrs {
+        TransFnAttrs {
+            flags: TransFnAttrFlags::empty(),
+            export_name: None,
+        }
+    }
+
+    /// True if the item is given a symbol name that isn't subject to the
+    /// usual mangling -- `#[no_mangle]` or an explicit `#[export_name]` --
+    /// either of which makes it visible to the linker regardless of
+    /// reachability-based inference.
+    pub fn contains_extern_indicator(&self) -> bool {
+        self.flags.contains(TransFnAttrFlags::NO_MANGLE) || self.export_name.is_some()
+    }
+}
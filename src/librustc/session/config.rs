@@ -0,0 +1,46 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `DebuggingOptions` fields for the `-Z` flags that
+//! `librustc_trans::back` consults: `-Zemit-export-list`,
+//! `-Zexport-symbol-allow`, and `-Zexport-symbol-deny`.
+
+/// Debugging (`-Z`) options read by `back::export_list` and
+/// `back::symbol_export`.
+#[derive(Clone)]
+pub struct DebuggingOptions {
+    /// `-Zpgo-gen=PATH`: emit a profile-guided-optimization instrumented
+    /// binary.
+    pub pgo_gen: Option<String>,
+
+    /// `-Zemit-export-list=PATH`: write a linker export-control file (ELF
+    /// version script, `-exported_symbols_list`, or `.def`, depending on
+    /// target) listing this crate's exported symbols.
+    pub emit_export_list: Option<String>,
+
+    /// `-Zexport-symbol-allow=PATTERN`: repeatable. If non-empty, only
+    /// symbols matching one of these glob patterns are exported.
+    pub export_symbol_allow: Vec<String>,
+
+    /// `-Zexport-symbol-deny=PATTERN`: repeatable. Symbols matching any of
+    /// these glob patterns are dropped from the exported set.
+    pub export_symbol_deny: Vec<String>,
+}
+
+impl Default for DebuggingOptions {
+    fn default() -> DebuggingOptions {
+        DebuggingOptions {
+            pgo_gen: None,
+            emit_export_list: None,
+            export_symbol_allow: Vec::new(),
+            export_symbol_deny: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `phase_6_link_output`, the driver phase that runs once a crate's codegen
+//! units have all written their object files.
+
+use rustc::ty::TyCtxt;
+
+use rustc_trans::back::write;
+
+/// Phase 6. Called immediately after the codegen-unit object files from
+/// phase 5 have all been written. It calls `write::finish_codegen`, which
+/// writes a requested `-Zemit-export-list` file from the final
+/// exported-symbol set; this function does not itself invoke the native
+/// linker.
+pub fn phase_6_link_output<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
+    write::finish_codegen(tcx);
+}
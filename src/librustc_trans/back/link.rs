@@ -0,0 +1,26 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Link-time preparation that runs once translation has produced the final
+//! exported-symbol set, before the crate's objects are handed to the system
+//! linker.
+
+use rustc::ty::TyCtxt;
+
+use back::export_list;
+
+/// Called right before invoking the linker, from `back::write::finish_codegen`
+/// once every codegen unit has written its object file. Currently this only
+/// drives `-Zemit-export-list`.
+pub fn prepare_link<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
+    if let Err(e) = export_list::emit_export_list_if_requested(tcx) {
+        tcx.sess.fatal(&format!("error writing export list: {}", e));
+    }
+}
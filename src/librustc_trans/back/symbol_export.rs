@@ -9,6 +9,7 @@
 // except according to those terms.
 
 use rustc_data_structures::sync::Lrc;
+use std::cmp::Ordering;
 use std::sync::Arc;
 
 use monomorphize::Instance;
@@ -24,7 +25,6 @@ use rustc::ty::subst::Substs;
 use rustc::util::nodemap::{FxHashMap, DefIdMap};
 use rustc_allocator::ALLOCATOR_METHODS;
 use rustc_data_structures::indexed_vec::IndexVec;
-use std::collections::hash_map::Entry::*;
 
 pub type ExportedSymbols = FxHashMap<
     CrateNum,
@@ -140,13 +140,15 @@ fn reachable_non_generics_provider<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
                 //
                 // In general though we won't link right if these
                 // symbols are stripped, and LTO currently strips them.
-                if &*name == "rust_eh_personality" ||
-                   &*name == "rust_eh_register_frames" ||
-                   &*name == "rust_eh_unregister_frames" {
-                    SymbolExportLevel::C
-                } else {
-                    SymbolExportLevel::Rust
-                }
+                //
+                // An explicit `#[export_level]` still overrides this
+                // hardwired split, same as it overrides the extern/
+                // std-internal heuristic outside special runtime crates:
+                // thread the name-based fallback through
+                // `symbol_export_level_from_flags` as its "is_extern"
+                // input instead of bypassing the attribute check entirely.
+                let flags = tcx.trans_fn_attrs(def_id).flags;
+                symbol_export_level_from_flags(flags, is_hardwired_runtime_symbol(&name), false)
             } else {
                 symbol_export_level(tcx, def_id)
             };
@@ -157,6 +159,19 @@ fn reachable_non_generics_provider<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
         })
         .collect();
 
+    // Apply the user-requested `-Zexport-symbol-allow`/`-Zexport-symbol-deny`
+    // glob filters, shrinking the exported surface. The hardwired EH runtime
+    // symbols (`is_hardwired_runtime_symbol`) are exempt no matter what the
+    // patterns say: dropping them breaks unwinding. Skip the mangled-name computation entirely when
+    // neither list was passed, which is the common case and otherwise a
+    // needless `symbol_name` query for every reachable item on every build.
+    if export_filters_configured(tcx) {
+        reachable_non_generics.retain(|&def_id, _| {
+            let name = tcx.symbol_name(Instance::mono(tcx, def_id)).to_string();
+            is_hardwired_runtime_symbol(&name) || symbol_passes_filters(tcx, &name)
+        });
+    }
+
     if let Some(id) = *tcx.sess.derive_registrar_fn.get() {
         let def_id = tcx.hir.local_def_id(id);
         reachable_non_generics.insert(def_id, SymbolExportLevel::C);
@@ -208,17 +223,19 @@ fn exported_symbols_provider_local<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
 
     if let Some(_) = *tcx.sess.entry_fn.borrow() {
         let symbol_name = "main".to_string();
-        let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
-
-        symbols.push((exported_symbol, SymbolExportLevel::C));
+        if symbol_passes_filters(tcx, &symbol_name) {
+            let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
+            symbols.push((exported_symbol, SymbolExportLevel::C));
+        }
     }
 
     if tcx.sess.allocator_kind.get().is_some() {
         for method in ALLOCATOR_METHODS {
             let symbol_name = format!("__rust_{}", method.name);
-            let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
-
-            symbols.push((exported_symbol, SymbolExportLevel::Rust));
+            if symbol_passes_filters(tcx, &symbol_name) {
+                let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
+                symbols.push((exported_symbol, SymbolExportLevel::Rust));
+            }
         }
     }
 
@@ -231,16 +248,19 @@ fn exported_symbols_provider_local<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
             "__llvm_profile_filename",
         ];
         for sym in &PROFILER_WEAK_SYMBOLS {
-            let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(sym));
-            symbols.push((exported_symbol, SymbolExportLevel::C));
+            if symbol_passes_filters(tcx, sym) {
+                let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(sym));
+                symbols.push((exported_symbol, SymbolExportLevel::C));
+            }
         }
     }
 
     if tcx.sess.crate_types.borrow().contains(&config::CrateTypeDylib) {
         let symbol_name = metadata_symbol_name(tcx);
-        let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
-
-        symbols.push((exported_symbol, SymbolExportLevel::Rust));
+        if symbol_passes_filters(tcx, &symbol_name) {
+            let exported_symbol = ExportedSymbol::NoDefId(SymbolName::new(&symbol_name));
+            symbols.push((exported_symbol, SymbolExportLevel::Rust));
+        }
     }
 
     if tcx.share_generics() && tcx.local_crate_exports_generics() {
@@ -257,6 +277,18 @@ fn exported_symbols_provider_local<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
 
         let (_, cgus) = tcx.collect_and_partition_translation_items(LOCAL_CRATE);
 
+        // Every generic instantiation the local crate's codegen-unit
+        // partitioning actually kept (`cgus`) is, by construction, one it
+        // found no upstream source for. Track both the exact `(def_id,
+        // substs)` pairs regenerated and the `def_id`s the local crate is
+        // proven to call generically at all, so the reuse count below can be
+        // scoped to instantiations this crate has actual demand for. Only
+        // items that get past the linkage and visibility checks below are
+        // tracked; internal or (where visibility is needed) hidden local
+        // instantiations are counted in neither set.
+        let mut locally_regenerated: FxHashMap<(DefId, &'tcx Substs<'tcx>), ()> = FxHashMap();
+        let mut locally_called_def_ids: FxHashMap<DefId, ()> = FxHashMap();
+
         for (mono_item, &(linkage, visibility)) in cgus.iter()
                                                        .flat_map(|cgu| cgu.items().iter()) {
             if linkage != Linkage::External {
@@ -276,11 +308,46 @@ fn exported_symbols_provider_local<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
                 substs,
             }) = mono_item {
                 if substs.types().next().is_some() {
-                    symbols.push((ExportedSymbol::Generic(def_id, substs),
-                                  SymbolExportLevel::Rust));
+                    locally_regenerated.insert((def_id, substs), ());
+                    locally_called_def_ids.insert(def_id, ());
+                    let name = tcx.symbol_name(Instance::new(def_id, substs)).to_string();
+                    if symbol_passes_filters(tcx, &name) {
+                        symbols.push((ExportedSymbol::Generic(def_id, substs),
+                                      SymbolExportLevel::Rust));
+                    }
+                }
+            }
+        }
+
+        // `upstream_monomorphizations(LOCAL_CRATE)` is the whole-dependency-
+        // graph catalog of every `(def_id, substs)` *any* crate chose to
+        // export generically -- most of it has nothing to do with this
+        // crate. Counting every catalog entry this crate didn't regenerate
+        // as "reused" would count instantiations of functions this crate
+        // never calls at all. Restrict to `def_id`s we've just proven this
+        // crate calls generically (via `locally_called_def_ids`); within
+        // those, a catalog substs this crate didn't itself regenerate is a
+        // real candidate for having come from upstream instead. This still
+        // can't distinguish "reused" from "simply never instantiated here"
+        // for a *different* substs of an otherwise-called `def_id` -- that
+        // needs the collector's own instantiation-mode decision, which this
+        // query doesn't have access to -- but it no longer counts
+        // instantiations of functions unrelated to this crate.
+        let mut reused_monomorphizations = 0;
+        for (&def_id, substs_to_cnum) in tcx.upstream_monomorphizations(LOCAL_CRATE).iter() {
+            if !locally_called_def_ids.contains_key(&def_id) {
+                continue;
+            }
+            for &substs in substs_to_cnum.keys() {
+                if !locally_regenerated.contains_key(&(def_id, substs)) {
+                    reused_monomorphizations += 1;
                 }
             }
         }
+
+        debug!("upstream monomorphization reuse: {} instantiations regenerated \
+                locally, {} reused from an upstream crate",
+               locally_regenerated.len(), reused_monomorphizations);
     }
 
     // Sort so we get a stable incr. comp. hash.
@@ -316,34 +383,142 @@ fn upstream_monomorphizations_provider<'a, 'tcx>(
         cnum_stable_ids
     };
 
+    // Gather every candidate crate per (def_id, substs) first, rather than
+    // picking a winner as we go, so that when we do pick we can see how big
+    // a share of a def_id's instantiations each candidate crate already
+    // supplies.
+    let mut candidates: DefIdMap<FxHashMap<&'tcx Substs<'tcx>, Vec<CrateNum>>> = DefIdMap();
+
     for &cnum in cnums.iter() {
         for &(ref exported_symbol, _) in tcx.exported_symbols(cnum).iter() {
             if let &ExportedSymbol::Generic(def_id, substs) = exported_symbol {
-                let substs_map = instances.entry(def_id)
-                                          .or_insert_with(|| FxHashMap());
-
-                match substs_map.entry(substs) {
-                    Occupied(mut e) => {
-                        // If there are multiple monomorphizations available,
-                        // we select one deterministically.
-                        let other_cnum = *e.get();
-                        if cnum_stable_ids[other_cnum] > cnum_stable_ids[cnum] {
-                            e.insert(cnum);
-                        }
-                    }
-                    Vacant(e) => {
-                        e.insert(cnum);
-                    }
-                }
+                candidates.entry(def_id)
+                          .or_insert_with(FxHashMap)
+                          .entry(substs)
+                          .or_insert_with(Vec::new)
+                          .push(cnum);
+            }
+        }
+    }
+
+    // Debug counters exposing how often the selection below actually had a
+    // choice to make. These count candidate crates *offering* an
+    // instantiation, not how the local crate ends up using it -- that's a
+    // separate measure, logged against the real outcome in
+    // `exported_symbols_provider_local` once codegen-unit partitioning has
+    // run. These two just tell us how often the contribution tie-break below
+    // had multiple crates to choose between.
+    let mut multi_candidate_instantiations = 0;
+    let mut single_candidate_instantiations = 0;
+
+    for (def_id, substs_to_cnums) in candidates {
+        // How many instantiations of `def_id` does each candidate crate
+        // already contribute? Preferring the crate with the largest share
+        // clusters the local crate's re-use into fewer upstream objects
+        // instead of scattering it across many.
+        let mut contributions: FxHashMap<CrateNum, usize> = FxHashMap();
+        for cnums in substs_to_cnums.values() {
+            for &cnum in cnums {
+                *contributions.entry(cnum).or_insert(0) += 1;
             }
         }
+
+        let resolved: FxHashMap<&'tcx Substs<'tcx>, CrateNum> = substs_to_cnums
+            .into_iter()
+            .map(|(substs, cnums)| {
+                if cnums.len() > 1 {
+                    multi_candidate_instantiations += 1;
+                } else {
+                    single_candidate_instantiations += 1;
+                }
+
+                let winner = pick_upstream_monomorphization(&cnums, &contributions,
+                                                             &cnum_stable_ids);
+
+                (substs, winner)
+            })
+            .collect();
+
+        instances.insert(def_id, resolved);
     }
 
+    debug!("upstream monomorphizations: {} instantiations had multiple upstream \
+            candidates to cluster, {} came from a single crate",
+           multi_candidate_instantiations, single_candidate_instantiations);
+
     Lrc::new(instances.into_iter()
                       .map(|(key, value)| (key, Lrc::new(value)))
                       .collect())
 }
 
+// Picks, out of `cnums` (every upstream crate that offers this one
+// `(def_id, substs)` instantiation), the one contributing the largest share
+// of `def_id`'s other instantiations -- clustering re-use into fewer
+// upstream objects -- falling back to `cnum_stable_ids` to break a tie
+// deterministically. Kept free of `TyCtxt` so the tie-break logic itself can
+// be exercised without standing up a compilation session.
+fn pick_upstream_monomorphization(cnums: &[CrateNum],
+                                  contributions: &FxHashMap<CrateNum, usize>,
+                                  cnum_stable_ids: &IndexVec<CrateNum, Fingerprint>)
+                                  -> CrateNum {
+    let mut winner = cnums[0];
+    for &candidate in &cnums[1..] {
+        let prefer_candidate = match contributions[&candidate].cmp(&contributions[&winner]) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => cnum_stable_ids[candidate] < cnum_stable_ids[winner],
+        };
+        if prefer_candidate {
+            winner = candidate;
+        }
+    }
+    winner
+}
+
+#[cfg(test)]
+mod upstream_monomorphization_selection_tests {
+    use super::*;
+
+    fn stable_ids(count: usize) -> IndexVec<CrateNum, Fingerprint> {
+        let mut ids = IndexVec::from_elem_n(Fingerprint::ZERO, count);
+        for i in 0..count {
+            ids[i as CrateNum] = Fingerprint::new(i as u64, 0);
+        }
+        ids
+    }
+
+    #[test]
+    fn highest_contribution_crate_wins() {
+        let cnums: Vec<CrateNum> = vec![1, 2, 3];
+        let mut contributions = FxHashMap();
+        contributions.insert(1, 2);
+        contributions.insert(2, 5);
+        contributions.insert(3, 1);
+
+        assert_eq!(
+            pick_upstream_monomorphization(&cnums, &contributions, &stable_ids(4)),
+            2
+        );
+    }
+
+    #[test]
+    fn equal_contributions_fall_back_to_stable_crate_id_ordering() {
+        let cnums: Vec<CrateNum> = vec![3, 1, 2];
+        let mut contributions = FxHashMap();
+        contributions.insert(1, 4);
+        contributions.insert(2, 4);
+        contributions.insert(3, 4);
+
+        // All three contribute equally, so the crate with the lowest stable
+        // id (1, since `stable_ids` assigns id `i` to crate number `i`) wins
+        // regardless of iteration order.
+        assert_eq!(
+            pick_upstream_monomorphization(&cnums, &contributions, &stable_ids(4)),
+            1
+        );
+    }
+}
+
 fn upstream_monomorphizations_for_provider<'a, 'tcx>(
     tcx: TyCtxt<'a, 'tcx, 'tcx>,
     def_id: DefId)
@@ -377,6 +552,103 @@ pub fn provide_extern(providers: &mut Providers) {
     providers.upstream_monomorphizations_for = upstream_monomorphizations_for_provider;
 }
 
+// Symbols that are hardwired to stay exported regardless of
+// `-Zexport-symbol-allow`/`-Zexport-symbol-deny`: LTO currently strips
+// hidden-visibility symbols, and these are the ABI between a special
+// runtime crate and the rest of the world, so losing them breaks unwinding.
+fn is_hardwired_runtime_symbol(name: &str) -> bool {
+    name == "rust_eh_personality" ||
+    name == "rust_eh_register_frames" ||
+    name == "rust_eh_unregister_frames"
+}
+
+/// Whether either `-Zexport-symbol-allow` or `-Zexport-symbol-deny` was
+/// passed. Callers that only care about the filters' effect (dropping
+/// symbols) can use this to skip resolving a mangled name altogether in the
+/// common case where neither flag is in play.
+fn export_filters_configured(tcx: TyCtxt) -> bool {
+    let debugging_opts = &tcx.sess.opts.debugging_opts;
+    !debugging_opts.export_symbol_allow.is_empty() || !debugging_opts.export_symbol_deny.is_empty()
+}
+
+/// Applies `-Zexport-symbol-deny` and `-Zexport-symbol-allow` to a resolved,
+/// mangled symbol name. A symbol matching any deny pattern is dropped; if an
+/// allowlist is present, only symbols matching one of its patterns survive.
+fn symbol_passes_filters(tcx: TyCtxt, name: &str) -> bool {
+    let debugging_opts = &tcx.sess.opts.debugging_opts;
+
+    if debugging_opts.export_symbol_deny.iter().any(|pattern| glob_match(pattern, name)) {
+        return false;
+    }
+
+    debugging_opts.export_symbol_allow.is_empty() ||
+        debugging_opts.export_symbol_allow.iter().any(|pattern| glob_match(pattern, name))
+}
+
+// A small `*`/`?` glob matcher. Good enough for symbol-name allow/deny lists;
+// nothing here needs the full power (or the dependency) of a regex engine.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let mut dp = vec![vec![false; text.len() + 1]; pattern.len() + 1];
+    dp[0][0] = true;
+    for i in 1..=pattern.len() {
+        if pattern[i - 1] == '*' {
+            dp[i][0] = dp[i - 1][0];
+        }
+    }
+    for i in 1..=pattern.len() {
+        for j in 1..=text.len() {
+            dp[i][j] = match pattern[i - 1] {
+                '*' => dp[i - 1][j] || dp[i][j - 1],
+                '?' => dp[i - 1][j - 1],
+                c => dp[i - 1][j - 1] && c == text[j - 1],
+            };
+        }
+    }
+    dp[pattern.len()][text.len()]
+}
+
+#[cfg(test)]
+mod glob_match_tests {
+    use super::glob_match;
+
+    #[test]
+    fn exact_match_with_no_wildcards() {
+        assert!(glob_match("foo_bar", "foo_bar"));
+        assert!(!glob_match("foo_bar", "foo_baz"));
+    }
+
+    #[test]
+    fn star_matches_any_run_including_empty() {
+        assert!(glob_match("foo_*", "foo_bar"));
+        assert!(glob_match("foo_*", "foo_"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("foo_*", "bar_baz"));
+    }
+
+    #[test]
+    fn star_in_the_middle_matches_across_boundaries() {
+        assert!(glob_match("rust_eh_*_frames", "rust_eh_register_frames"));
+        assert!(glob_match("rust_eh_*_frames", "rust_eh_unregister_frames"));
+        assert!(!glob_match("rust_eh_*_frames", "rust_eh_personality"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_character() {
+        assert!(glob_match("foo?", "foo1"));
+        assert!(!glob_match("foo?", "foo"));
+        assert!(!glob_match("foo?", "foo12"));
+    }
+
+    #[test]
+    fn empty_pattern_only_matches_empty_text() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "x"));
+    }
+}
+
 fn symbol_export_level(tcx: TyCtxt, sym_def_id: DefId) -> SymbolExportLevel {
     // We export anything that's not mangled at the "C" layer as it probably has
     // to do with ABI concerns. We do not, however, apply such treatment to
@@ -387,9 +659,67 @@ fn symbol_export_level(tcx: TyCtxt, sym_def_id: DefId) -> SymbolExportLevel {
     let is_extern = trans_fn_attrs.contains_extern_indicator();
     let std_internal = trans_fn_attrs.flags.contains(TransFnAttrFlags::RUSTC_STD_INTERNAL_SYMBOL);
 
+    symbol_export_level_from_flags(trans_fn_attrs.flags, is_extern, std_internal)
+}
+
+// `#[export_level]` wins outright over the extern/std-internal heuristic
+// when present; only the precedence between the two needs checking here, so
+// this stays a pure function of the already-collected flags rather than
+// re-deriving `is_extern`/`std_internal` from a `TyCtxt`.
+fn symbol_export_level_from_flags(flags: TransFnAttrFlags,
+                                   is_extern: bool,
+                                   std_internal: bool)
+                                   -> SymbolExportLevel {
+    // A `#[export_level = "c"]` or `#[export_level = "rust"]` attribute on the
+    // item always wins: it lets authors demote a `#[no_mangle]` function that
+    // is really just internal plumbing, or promote a Rust-ABI symbol that a
+    // downstream C consumer needs to survive LTO. Only fall back to the
+    // heuristic below when the author hasn't expressed an opinion. See
+    // `librustc_typeck::collect::export_level_flags` for where the attribute
+    // is parsed into these two flags.
+    if flags.contains(TransFnAttrFlags::EXPORT_LEVEL_C) {
+        return SymbolExportLevel::C;
+    }
+    if flags.contains(TransFnAttrFlags::EXPORT_LEVEL_RUST) {
+        return SymbolExportLevel::Rust;
+    }
+
     if is_extern && !std_internal {
         SymbolExportLevel::C
     } else {
         SymbolExportLevel::Rust
     }
 }
+
+#[cfg(test)]
+mod export_level_tests {
+    use super::*;
+
+    #[test]
+    fn export_level_c_attribute_overrides_std_internal() {
+        assert_eq!(
+            symbol_export_level_from_flags(TransFnAttrFlags::EXPORT_LEVEL_C, false, true),
+            SymbolExportLevel::C
+        );
+    }
+
+    #[test]
+    fn export_level_rust_attribute_overrides_extern_indicator() {
+        assert_eq!(
+            symbol_export_level_from_flags(TransFnAttrFlags::EXPORT_LEVEL_RUST, true, false),
+            SymbolExportLevel::Rust
+        );
+    }
+
+    #[test]
+    fn falls_back_to_heuristic_when_no_override_is_present() {
+        assert_eq!(
+            symbol_export_level_from_flags(TransFnAttrFlags::empty(), true, false),
+            SymbolExportLevel::C
+        );
+        assert_eq!(
+            symbol_export_level_from_flags(TransFnAttrFlags::empty(), false, false),
+            SymbolExportLevel::Rust
+        );
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The tail end of code generation: once every codegen unit for this crate
+//! has finished producing its object file, `finish_codegen` calls
+//! `back::link::prepare_link`, which writes a requested `-Zemit-export-list`
+//! file. Nothing here links the objects into the final artifact.
+//! `finish_codegen` is called by `rustc_driver::driver::phase_6_link_output`.
+
+use rustc::ty::TyCtxt;
+
+use back::link;
+
+/// Called once all of a crate's codegen units have written their object
+/// files, before those objects are handed to the system linker.
+/// `link::prepare_link` runs first so any requested export-control file
+/// (`-Zemit-export-list`) reflects the final exported-symbol set.
+pub fn finish_codegen<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>) {
+    link::prepare_link(tcx);
+}
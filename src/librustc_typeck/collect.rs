@@ -0,0 +1,105 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `trans_fn_attrs` query provider: collects the translation-affecting
+//! attributes (`#[no_mangle]`, `#[export_name]`, `#[rustc_std_internal_symbol]`,
+//! `#[export_level]`) on a local item into the `TransFnAttrs` that
+//! `back::symbol_export::symbol_export_level` reads back off `tcx`.
+
+use rustc::hir::{TransFnAttrFlags, TransFnAttrs};
+use rustc::hir::def_id::DefId;
+use rustc::ty::TyCtxt;
+use rustc::ty::maps::Providers;
+use syntax::ast;
+use syntax::symbol::Symbol;
+
+/// Parses a `#[export_level = "c" | "rust"]` attribute on a translated item,
+/// if present, into the matching `TransFnAttrFlags` bits. This lets an item
+/// override the automatic export-level inference in
+/// `back::symbol_export::symbol_export_level`: `"c"` forces the `C` export
+/// level regardless of mangling, `"rust"` forces `Rust`. Any other value is
+/// rejected with an error at the attribute's span, as is repeating the
+/// attribute with a conflicting value.
+pub fn export_level_flags<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>,
+                                     attrs: &[ast::Attribute])
+                                     -> TransFnAttrFlags {
+    let mut flags = TransFnAttrFlags::empty();
+
+    for attr in attrs {
+        if !attr.check_name("export_level") {
+            continue;
+        }
+
+        match attr.value_str() {
+            Some(v) if v == Symbol::intern("c") => {
+                if flags.contains(TransFnAttrFlags::EXPORT_LEVEL_RUST) {
+                    tcx.sess.span_err(
+                        attr.span,
+                        "conflicting `#[export_level]` overrides: already set to `\"rust\"`",
+                    );
+                } else {
+                    flags |= TransFnAttrFlags::EXPORT_LEVEL_C;
+                }
+            }
+            Some(v) if v == Symbol::intern("rust") => {
+                if flags.contains(TransFnAttrFlags::EXPORT_LEVEL_C) {
+                    tcx.sess.span_err(
+                        attr.span,
+                        "conflicting `#[export_level]` overrides: already set to `\"c\"`",
+                    );
+                } else {
+                    flags |= TransFnAttrFlags::EXPORT_LEVEL_RUST;
+                }
+            }
+            _ => {
+                tcx.sess.span_err(
+                    attr.span,
+                    "`#[export_level]` must be either `\"c\"` or `\"rust\"`",
+                );
+            }
+        }
+    }
+
+    flags
+}
+
+/// Query provider for `tcx.trans_fn_attrs(def_id)`: collects every
+/// translation-affecting attribute on `id` into a `TransFnAttrs`. `#[no_mangle]`
+/// and `#[export_name]` are what `TransFnAttrs::contains_extern_indicator`
+/// reports, `#[rustc_std_internal_symbol]` marks standard-library plumbing
+/// that should stay at `Rust` export level despite being `#[no_mangle]`d, and
+/// `#[export_level]` (see above) is layered on top of those: it overrides the
+/// C/Rust *level* `symbol_export_level` derives from them, not whether the
+/// item is externally visible or std-internal in the first place.
+fn trans_fn_attrs<'a, 'tcx>(tcx: TyCtxt<'a, 'tcx, 'tcx>, id: DefId) -> TransFnAttrs {
+    let attrs = tcx.get_attrs(id);
+    let mut trans_fn_attrs = TransFnAttrs::new();
+
+    for attr in attrs.iter() {
+        if attr.check_name("no_mangle") {
+            trans_fn_attrs.flags |= TransFnAttrFlags::NO_MANGLE;
+        } else if attr.check_name("rustc_std_internal_symbol") {
+            trans_fn_attrs.flags |= TransFnAttrFlags::RUSTC_STD_INTERNAL_SYMBOL;
+        } else if attr.check_name("export_name") {
+            trans_fn_attrs.export_name = attr.value_str();
+        }
+    }
+
+    trans_fn_attrs.flags |= export_level_flags(tcx, &attrs);
+
+    trans_fn_attrs
+}
+
+pub fn provide(providers: &mut Providers) {
+    *providers = Providers {
+        trans_fn_attrs,
+        ..*providers
+    };
+}